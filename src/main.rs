@@ -1,13 +1,13 @@
 use axum::{
     extract::{Path, Query},
-    http::{StatusCode, HeaderMap},
+    http::{HeaderMap, StatusCode},
     routing::{get, post},
     Json, Router,
 };
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::{env};
-use redis::{AsyncCommands, RedisResult};
+use redis::AsyncCommands;
 use std::sync::Arc;
 use axum::body::{Body, to_bytes};
 use axum::extract::{Host, State};
@@ -15,7 +15,40 @@ use axum::http::Request;
 use axum::response::Redirect;
 use rand::distr::Alphanumeric;
 use rand::Rng;
+use tower_http::trace::TraceLayer;
 use tracing::{error, info};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+
+mod outcome;
+use outcome::ApiError;
+
+mod redis_backend;
+use redis_backend::{build_redis_backend, RedisBackend, RedisConn};
+
+mod scripts;
+use scripts::{get_url_and_incr_clicks_script, reserve_hash_key_script, reserve_key_script};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+
+#[cfg(test)]
+mod test_support;
+
+const LINK_TTL_SECONDS: u64 = 60 * 60 * 24 * 30;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum KeyMode {
+    Random,
+    Hash,
+}
+
+impl KeyMode {
+    fn from_env() -> Self {
+        match env::var("KEY_MODE").as_deref() {
+            Ok("hash") => KeyMode::Hash,
+            _ => KeyMode::Random,
+        }
+    }
+}
 
 #[derive(Deserialize)]
 struct CreateLinkRequest {
@@ -33,42 +66,49 @@ struct StatsQuery {
     token: Option<String>,
 }
 
-
-async fn redis_connection(redis_client: &redis::Client) -> RedisResult<redis::aio::MultiplexedConnection> {
-    redis_client
-        .get_multiplexed_async_connection().await
-}
-
 async fn generate_link(
     State(state): State<Arc<AppState>>,
     Host(hostname): Host,
     headers: HeaderMap,
     request: Request<Body>,
-) -> Result<Json<CreateLinkResponse>, StatusCode> {
+) -> Result<Json<CreateLinkResponse>, ApiError> {
     let scheme = request.uri().scheme_str().unwrap_or("http").to_string();
 
     let req_body = request.into_body();
-    let data = to_bytes(req_body, 10000).await.expect("Unable to read data");
-    let payload: CreateLinkRequest = serde_json::from_slice(&data).unwrap();
+    let data = to_bytes(req_body, 10000)
+        .await
+        .map_err(|_| ApiError::BadRequest("request body too large or unreadable".to_string()))?;
+    let payload: CreateLinkRequest = serde_json::from_slice(&data)
+        .map_err(|_| ApiError::BadRequest("invalid JSON body".to_string()))?;
 
-    let auth_token = env::var("AUTH_TOKEN").map_err(|_| StatusCode::UNAUTHORIZED)?;
-    let req_auth_token = headers.get("Authorization").and_then(|v| v.to_str().ok()).ok_or(StatusCode::UNAUTHORIZED)?;
+    let auth_token = env::var("AUTH_TOKEN").map_err(|_| ApiError::Unauthorized)?;
+    let req_auth_token = headers.get("Authorization").and_then(|v| v.to_str().ok()).ok_or(ApiError::Unauthorized)?;
     if auth_token != req_auth_token {
-        return Err(StatusCode::UNAUTHORIZED);
+        return Err(ApiError::Unauthorized);
     }
 
-    let mut redis_conn = redis_connection(&state.redis_client).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let mut redis_conn = state.redis_backend.checkout().await?;
+
+    let (short_key, token) = match state.key_mode {
+        KeyMode::Random => {
+            let token = rand_string(24);
+            let short_key = generate_and_save_key(&mut redis_conn, &payload.url, &token)
+                .await
+                .map_err(ApiError::Internal)?;
 
-    let short_key = generate_and_save_key(&mut redis_conn).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    let token = rand_string(24);
+            (short_key, token)
+        }
+        KeyMode::Hash => {
+            let token = rand_string(24);
+            generate_and_save_hash_key(&mut redis_conn, &payload.url, &token)
+                .await
+                .map_err(ApiError::Internal)?
+        }
+    };
 
     let short_url = format!("{scheme}://{hostname}/{short_key}");
     let stats_url = format!("{short_url}/stats?token={token}");
 
-    redis_conn.hset(&short_key, "url", payload.url).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    redis_conn.hset(&short_key, "token", token).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    redis_conn.hset(&short_key, "clicks", 0).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
     Ok(Json(CreateLinkResponse {
         short_url,
         stats_url,
@@ -76,20 +116,26 @@ async fn generate_link(
 }
 
 async fn generate_and_save_key(
-    redis_conn: &mut redis::aio::MultiplexedConnection
+    redis_conn: &mut RedisConn<'_>,
+    url: &str,
+    token: &str,
 ) -> Result<String, String> {
     for attempt in 0..3 {
         let short_key: String = rand_string(6);
 
-        let key_exists: bool = redis_conn.exists(&short_key).await.map_err(|e| {
-            error!("Redis error on check: {:?}", e);
-            "Redis check error".to_string()
-        })?;
-
-        if !key_exists {
-            redis_conn.hset(&short_key, "url", "").await.map_err(|e| format!("{:?}", e))?;
-            redis_conn.expire(&short_key, 60 * 60 * 24 * 30).await.map_err(|e| format!("{:?}", e))?;
-
+        let reserved: i32 = reserve_key_script()
+            .key(&short_key)
+            .arg(url)
+            .arg(token)
+            .arg(LINK_TTL_SECONDS)
+            .invoke_async(redis_conn)
+            .await
+            .map_err(|e| {
+                error!("Redis error on reserve: {:?}", e);
+                "Redis reserve error".to_string()
+            })?;
+
+        if reserved == 1 {
             return Ok(short_key);
         }
 
@@ -102,8 +148,82 @@ async fn generate_and_save_key(
     Err("Failed to generate a unique key after 3 attempts".to_string())
 }
 
+/// Derives a short key from `url`'s Blake3 digest and atomically reserves it.
+/// Submitting the same url again returns the same `(short_key, token)` pair
+/// instead of creating a duplicate. On a hash-prefix collision with a
+/// different url, the prefix is lengthened by a byte and retried.
+async fn generate_and_save_hash_key(
+    redis_conn: &mut RedisConn<'_>,
+    url: &str,
+    token: &str,
+) -> Result<(String, String), String> {
+    let digest = blake3::hash(url.trim().as_bytes());
+    let digest_bytes = digest.as_bytes();
+
+    let mut prefix_len = 6;
+    loop {
+        let short_key = URL_SAFE_NO_PAD.encode(&digest_bytes[..prefix_len]);
+
+        let (status, _existing_url, existing_token): (i32, String, String) = reserve_hash_key_script()
+            .key(&short_key)
+            .arg(url)
+            .arg(token)
+            .arg(LINK_TTL_SECONDS)
+            .invoke_async(redis_conn)
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+
+        match status {
+            1 => return Ok((short_key, token.to_string())),
+            0 => return Ok((short_key, existing_token)),
+            _ if prefix_len < digest_bytes.len() => prefix_len += 1,
+            _ => return Err("Failed to derive a unique hash key from the full digest".to_string()),
+        }
+    }
+}
+
+/// Forces a hash-prefix collision on the first candidate key and checks that
+/// `generate_and_save_hash_key` retries with a longer prefix instead of
+/// clobbering the existing occupant. Uses a real Redis (see
+/// `crate::test_support`).
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_redis_backend;
+
+    #[tokio::test]
+    async fn hash_key_retries_past_a_colliding_prefix() {
+        let backend = test_redis_backend().await;
+        let mut conn = backend.checkout().await.unwrap();
+
+        let url = "https://example.com/collision-test";
+        let digest = blake3::hash(url.trim().as_bytes());
+        let digest_bytes = digest.as_bytes();
+        let colliding_key = URL_SAFE_NO_PAD.encode(&digest_bytes[..6]);
+        let lengthened_key = URL_SAFE_NO_PAD.encode(&digest_bytes[..7]);
+
+        let _: () = conn.del(&colliding_key).await.unwrap();
+        let _: () = conn.del(&lengthened_key).await.unwrap();
+        let _: () = conn.hset(&colliding_key, "url", "https://other.example.com").await.unwrap();
+        let _: () = conn.hset(&colliding_key, "token", "occupant-token").await.unwrap();
+
+        let (short_key, token) = generate_and_save_hash_key(&mut conn, url, "new-token")
+            .await
+            .unwrap();
+
+        assert_eq!(short_key, lengthened_key);
+        assert_eq!(token, "new-token");
+
+        let occupant_url: String = conn.hget(&colliding_key, "url").await.unwrap();
+        assert_eq!(occupant_url, "https://other.example.com");
+
+        let _: () = conn.del(&colliding_key).await.unwrap();
+        let _: () = conn.del(&lengthened_key).await.unwrap();
+    }
+}
+
 fn rand_string(n: usize) -> String {
-    rand::thread_rng()
+    rand::rng()
         .sample_iter(&Alphanumeric)
         .take(n)
         .map(char::from)
@@ -113,12 +233,15 @@ fn rand_string(n: usize) -> String {
 async fn proxy_link(
     Path(short_key): Path<String>,
     State(state): State<Arc<AppState>>,
-) -> Result<Redirect, StatusCode> {
-    let mut redis_conn = redis_connection(&state.redis_client).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+) -> Result<Redirect, ApiError> {
+    let mut redis_conn = state.redis_backend.checkout().await?;
 
-    let original_url: String = redis_conn.hget(&short_key, "url").await.map_err(|_| StatusCode::NOT_FOUND)?;
+    let original_url: Option<String> = get_url_and_incr_clicks_script()
+        .key(&short_key)
+        .invoke_async(&mut redis_conn)
+        .await?;
 
-    redis_conn.hincr(&short_key, "clicks", 1).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let original_url = original_url.ok_or(ApiError::NotFound)?;
 
     Ok(Redirect::temporary(&original_url))
 }
@@ -127,18 +250,18 @@ async fn get_stats(
     Path(short_key): Path<String>,
     Query(params): Query<StatsQuery>,
     State(state): State<Arc<AppState>>,
-) -> Result<Json<HashMap<String, usize>>, StatusCode> {
-    let mut redis_conn = redis_connection(&state.redis_client).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+) -> Result<Json<HashMap<String, usize>>, ApiError> {
+    let mut redis_conn = state.redis_backend.checkout().await?;
 
-    let stored_token: String = redis_conn.hget(&short_key, "token").await.map_err(|_| StatusCode::NOT_FOUND)?;
+    let stored_token: String = redis_conn.hget(&short_key, "token").await.map_err(|_| ApiError::NotFound)?;
 
     match params.token {
-        None => return Err(StatusCode::NOT_FOUND),
-        Some(token) if token != stored_token => return Err(StatusCode::UNAUTHORIZED),
+        None => return Err(ApiError::NotFound),
+        Some(token) if token != stored_token => return Err(ApiError::Unauthorized),
         _ => {},
     }
 
-    let clicks: usize = redis_conn.hget(&short_key, "clicks").await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let clicks: usize = redis_conn.hget(&short_key, "clicks").await?;
 
     let mut stats = HashMap::new();
     stats.insert("clicks".to_string(), clicks);
@@ -146,21 +269,240 @@ async fn get_stats(
     Ok(Json(stats))
 }
 
+#[derive(Deserialize)]
+struct UpdateLinkRequest {
+    url: String,
+    #[serde(default)]
+    reset_clicks: bool,
+}
+
+async fn update_link(
+    Path(short_key): Path<String>,
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    request: Request<Body>,
+) -> Result<StatusCode, ApiError> {
+    let req_body = request.into_body();
+    let data = to_bytes(req_body, 10000)
+        .await
+        .map_err(|_| ApiError::BadRequest("request body too large or unreadable".to_string()))?;
+    let payload: UpdateLinkRequest = serde_json::from_slice(&data)
+        .map_err(|_| ApiError::BadRequest("invalid JSON body".to_string()))?;
+
+    let mut redis_conn = state.redis_backend.checkout().await?;
+
+    authorize_link(&mut redis_conn, &short_key, &headers).await?;
+
+    let _: () = redis_conn.hset(&short_key, "url", payload.url).await?;
+    if payload.reset_clicks {
+        let _: () = redis_conn.hset(&short_key, "clicks", 0).await?;
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn delete_link(
+    Path(short_key): Path<String>,
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<StatusCode, ApiError> {
+    let mut redis_conn = state.redis_backend.checkout().await?;
+
+    authorize_link(&mut redis_conn, &short_key, &headers).await?;
+
+    let _: () = redis_conn.del(&short_key).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Authorizes a management request against `short_key`'s stats `token` or the
+/// global `AUTH_TOKEN`. Returns `ApiError::NotFound` if the key doesn't exist.
+async fn authorize_link(
+    redis_conn: &mut RedisConn<'_>,
+    short_key: &str,
+    headers: &HeaderMap,
+) -> Result<(), ApiError> {
+    let stored_token: String = redis_conn.hget(short_key, "token").await.map_err(|_| ApiError::NotFound)?;
+
+    let req_token = headers.get("Authorization").and_then(|v| v.to_str().ok()).ok_or(ApiError::Unauthorized)?;
+
+    let global_auth_token = env::var("AUTH_TOKEN").ok();
+    if req_token != stored_token && global_auth_token.as_deref() != Some(req_token) {
+        return Err(ApiError::Unauthorized);
+    }
+
+    Ok(())
+}
+
+/// Exercises update_link/delete_link/authorize_link against a real Redis
+/// (see `crate::test_support`). These read/write the process-wide
+/// `AUTH_TOKEN` env var, so they share a lock to avoid interfering with each
+/// other when run in parallel.
+#[cfg(test)]
+mod link_management_tests {
+    use super::*;
+    use crate::test_support::test_redis_backend;
+    use tokio::sync::Mutex;
+
+    static AUTH_TOKEN_LOCK: Mutex<()> = Mutex::const_new(());
+
+    fn update_request(body: &str, token: Option<&str>) -> (HeaderMap, Request<Body>) {
+        let mut headers = HeaderMap::new();
+        if let Some(token) = token {
+            headers.insert("Authorization", token.parse().unwrap());
+        }
+        let request = Request::builder().body(Body::from(body.to_string())).unwrap();
+        (headers, request)
+    }
+
+    async fn seed_link(conn: &mut RedisConn<'_>, key: &str, url: &str, token: &str) {
+        let _: () = conn.del(key).await.unwrap();
+        let _: () = conn.hset(key, "url", url).await.unwrap();
+        let _: () = conn.hset(key, "token", token).await.unwrap();
+        let _: () = conn.hset(key, "clicks", 0).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn update_link_rejects_wrong_token() {
+        let _guard = AUTH_TOKEN_LOCK.lock().await;
+        env::remove_var("AUTH_TOKEN");
+
+        let backend = test_redis_backend().await;
+        let key = "test:update_link:wrong_token";
+        seed_link(&mut backend.checkout().await.unwrap(), key, "https://original.example.com", "correct-token").await;
+
+        let (headers, request) = update_request(r#"{"url":"https://updated.example.com"}"#, Some("wrong-token"));
+        let state = Arc::new(AppState { redis_backend: backend, key_mode: KeyMode::Random });
+
+        let result = update_link(Path(key.to_string()), State(state.clone()), headers, request).await;
+        assert!(matches!(result, Err(ApiError::Unauthorized)));
+
+        let _: () = state.redis_backend.checkout().await.unwrap().del(key).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn update_link_reports_missing_key_as_not_found() {
+        let _guard = AUTH_TOKEN_LOCK.lock().await;
+        env::remove_var("AUTH_TOKEN");
+
+        let backend = test_redis_backend().await;
+        let key = "test:update_link:missing";
+        let _: () = backend.checkout().await.unwrap().del(key).await.unwrap();
+
+        let (headers, request) = update_request(r#"{"url":"https://updated.example.com"}"#, Some("anything"));
+        let state = Arc::new(AppState { redis_backend: backend, key_mode: KeyMode::Random });
+
+        let result = update_link(Path(key.to_string()), State(state), headers, request).await;
+        assert!(matches!(result, Err(ApiError::NotFound)));
+    }
+
+    #[tokio::test]
+    async fn update_link_with_correct_token_changes_the_url() {
+        let _guard = AUTH_TOKEN_LOCK.lock().await;
+        env::remove_var("AUTH_TOKEN");
+
+        let backend = test_redis_backend().await;
+        let key = "test:update_link:correct_token";
+        seed_link(&mut backend.checkout().await.unwrap(), key, "https://original.example.com", "correct-token").await;
+
+        let (headers, request) = update_request(r#"{"url":"https://updated.example.com"}"#, Some("correct-token"));
+        let state = Arc::new(AppState { redis_backend: backend, key_mode: KeyMode::Random });
+
+        let result = update_link(Path(key.to_string()), State(state.clone()), headers, request).await;
+        assert_eq!(result.unwrap(), StatusCode::NO_CONTENT);
+
+        let mut conn = state.redis_backend.checkout().await.unwrap();
+        let url: String = conn.hget(key, "url").await.unwrap();
+        assert_eq!(url, "https://updated.example.com");
+
+        let _: () = conn.del(key).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn update_link_also_accepts_the_global_auth_token() {
+        let _guard = AUTH_TOKEN_LOCK.lock().await;
+        env::set_var("AUTH_TOKEN", "global-secret");
+
+        let backend = test_redis_backend().await;
+        let key = "test:update_link:global_token";
+        seed_link(&mut backend.checkout().await.unwrap(), key, "https://original.example.com", "per-link-token").await;
+
+        let (headers, request) = update_request(r#"{"url":"https://updated.example.com"}"#, Some("global-secret"));
+        let state = Arc::new(AppState { redis_backend: backend, key_mode: KeyMode::Random });
+
+        let result = update_link(Path(key.to_string()), State(state.clone()), headers, request).await;
+        assert_eq!(result.unwrap(), StatusCode::NO_CONTENT);
+
+        let _: () = state.redis_backend.checkout().await.unwrap().del(key).await.unwrap();
+        env::remove_var("AUTH_TOKEN");
+    }
+
+    #[tokio::test]
+    async fn delete_link_removes_the_hash() {
+        let _guard = AUTH_TOKEN_LOCK.lock().await;
+        env::remove_var("AUTH_TOKEN");
+
+        let backend = test_redis_backend().await;
+        let key = "test:delete_link:removes";
+        seed_link(&mut backend.checkout().await.unwrap(), key, "https://original.example.com", "correct-token").await;
+
+        let mut headers = HeaderMap::new();
+        headers.insert("Authorization", "correct-token".parse().unwrap());
+        let state = Arc::new(AppState { redis_backend: backend, key_mode: KeyMode::Random });
+
+        let result = delete_link(Path(key.to_string()), State(state.clone()), headers).await;
+        assert_eq!(result.unwrap(), StatusCode::NO_CONTENT);
+
+        let exists: bool = state.redis_backend.checkout().await.unwrap().exists(key).await.unwrap();
+        assert!(!exists);
+    }
+}
+
 pub struct AppState {
-    pub redis_client: redis::Client,
+    pub redis_backend: RedisBackend,
+    pub key_mode: KeyMode,
+}
+
+/// Installs an env-filtered `fmt` subscriber, plus a Sentry tracing layer
+/// when `SENTRY_DSN` is set so 5xx/error-level events are also captured there.
+fn init_tracing() {
+    let filter = tracing_subscriber::EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info"));
+    let sentry_layer = env::var("SENTRY_DSN").is_ok().then(sentry_tracing::layer);
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(tracing_subscriber::fmt::layer())
+        .with(sentry_layer)
+        .init();
 }
 
 #[tokio::main]
 async fn main() {
+    init_tracing();
+
+    let _sentry_guard = env::var("SENTRY_DSN").ok().map(|dsn| {
+        sentry::init((
+            dsn,
+            sentry::ClientOptions::default()
+                .maybe_release(sentry::release_name!())
+                .traces_sample_rate(1.0),
+        ))
+    });
+
     let redis_url = env::var("REDIS_URL").unwrap_or("redis://127.0.0.1/".to_string());
-    let redis_client = redis::Client::open(redis_url).unwrap();
-    let app_state = AppState { redis_client };
+    let redis_backend = build_redis_backend(&redis_url).await;
+    let key_mode = KeyMode::from_env();
+    let app_state = AppState { redis_backend, key_mode };
 
     let router = Router::new()
-        .route("/:short_key", get(proxy_link))
+        .route("/:short_key", get(proxy_link).put(update_link).delete(delete_link))
         .route("/:short_key/stats", get(get_stats))
         .route("/generate", post(generate_link))
-        .with_state(Arc::new(app_state));
+        .with_state(Arc::new(app_state))
+        .layer(sentry_tower::NewSentryLayer::<Request<Body>>::new_from_top())
+        .layer(sentry_tower::SentryHttpLayer::new())
+        .layer(TraceLayer::new_for_http());
 
     let host = env::var("HOST").unwrap_or("127.0.0.1".to_string());
     let port = env::var("PORT").unwrap_or("3000".to_string());
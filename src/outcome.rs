@@ -0,0 +1,55 @@
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::Serialize;
+use tracing::error;
+
+/// The error type returned by every handler. Carries enough detail to pick a
+/// status code and to hand the client a machine-readable payload.
+#[derive(Debug)]
+pub enum ApiError {
+    BadRequest(String),
+    Unauthorized,
+    NotFound,
+    RedisUnavailable,
+    Internal(String),
+}
+
+#[derive(Serialize)]
+struct ErrorBody {
+    error: &'static str,
+    message: String,
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, error, message) = match self {
+            ApiError::BadRequest(message) => (StatusCode::BAD_REQUEST, "bad_request", message),
+            ApiError::Unauthorized => (
+                StatusCode::UNAUTHORIZED,
+                "unauthorized",
+                "invalid or missing credentials".to_string(),
+            ),
+            ApiError::NotFound => (
+                StatusCode::NOT_FOUND,
+                "not_found",
+                "short link not found".to_string(),
+            ),
+            ApiError::RedisUnavailable => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "redis_unavailable",
+                "redis is temporarily unavailable".to_string(),
+            ),
+            ApiError::Internal(message) => (StatusCode::INTERNAL_SERVER_ERROR, "internal", message),
+        };
+
+        (status, Json(ErrorBody { error, message })).into_response()
+    }
+}
+
+impl From<redis::RedisError> for ApiError {
+    fn from(err: redis::RedisError) -> Self {
+        error!("Redis error: {:?}", err);
+        ApiError::Internal("internal error".to_string())
+    }
+}
@@ -0,0 +1,125 @@
+use std::time::Duration;
+
+use bb8::Pool;
+use bb8_redis::RedisConnectionManager;
+use redis::aio::ConnectionLike;
+use redis::cluster::ClusterClient;
+use redis::cluster_async::ClusterConnection;
+use redis::{Cmd, Pipeline, RedisFuture, Value};
+use std::env;
+use tracing::{error, info};
+
+use crate::outcome::ApiError;
+
+pub type RedisPool = Pool<RedisConnectionManager>;
+
+/// Selects between a pooled single-node connection and a cluster connection,
+/// so the rest of the app doesn't need to know which one is in play.
+pub enum RedisBackend {
+    Single(RedisPool),
+    Cluster(ClusterConnection),
+}
+
+impl RedisBackend {
+    pub async fn checkout(&self) -> Result<RedisConn<'_>, ApiError> {
+        match self {
+            RedisBackend::Single(pool) => {
+                let conn = pool.get().await.map_err(|e| {
+                    error!("Redis pool checkout error: {:?}", e);
+                    ApiError::RedisUnavailable
+                })?;
+                Ok(RedisConn::Single(conn))
+            }
+            RedisBackend::Cluster(conn) => Ok(RedisConn::Cluster(conn.clone())),
+        }
+    }
+}
+
+/// A checked-out connection from either backend. Implements `ConnectionLike`
+/// so scripts and `AsyncCommands` calls work the same way against both.
+pub enum RedisConn<'a> {
+    Single(bb8::PooledConnection<'a, RedisConnectionManager>),
+    Cluster(ClusterConnection),
+}
+
+impl ConnectionLike for RedisConn<'_> {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+        match self {
+            RedisConn::Single(conn) => conn.req_packed_command(cmd),
+            RedisConn::Cluster(conn) => conn.req_packed_command(cmd),
+        }
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        cmd: &'a Pipeline,
+        offset: usize,
+        count: usize,
+    ) -> RedisFuture<'a, Vec<Value>> {
+        match self {
+            RedisConn::Single(conn) => conn.req_packed_commands(cmd, offset, count),
+            RedisConn::Cluster(conn) => conn.req_packed_commands(cmd, offset, count),
+        }
+    }
+
+    fn get_db(&self) -> i64 {
+        match self {
+            RedisConn::Single(conn) => conn.get_db(),
+            RedisConn::Cluster(conn) => conn.get_db(),
+        }
+    }
+}
+
+async fn build_redis_pool(redis_url: &str) -> RedisPool {
+    let manager = RedisConnectionManager::new(redis_url).unwrap();
+
+    let max_size = env::var("REDIS_POOL_MAX_SIZE")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(10);
+    let connection_timeout_secs = env::var("REDIS_POOL_CONNECTION_TIMEOUT_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(5);
+
+    Pool::builder()
+        .max_size(max_size)
+        .connection_timeout(Duration::from_secs(connection_timeout_secs))
+        .build(manager)
+        .await
+        .unwrap()
+}
+
+/// Builds the configured backend. `REDIS_CLUSTER=true` builds a cluster
+/// client against the comma-separated node list in `REDIS_CLUSTER_NODES`
+/// (falling back to `redis_url` as the sole seed node); otherwise a pooled
+/// single-node connection is used. `rediss://` URLs are passed through
+/// unchanged; the `redis` crate's `tokio-rustls-comp` feature (enabled in
+/// Cargo.toml) is what actually makes it open a TLS connection instead of
+/// erroring on the unsupported scheme.
+pub async fn build_redis_backend(redis_url: &str) -> RedisBackend {
+    let use_cluster = env::var("REDIS_CLUSTER")
+        .map(|v| v == "true")
+        .unwrap_or(false);
+
+    if redis_url.starts_with("rediss://") {
+        info!("Connecting to Redis over TLS");
+    }
+
+    if !use_cluster {
+        return RedisBackend::Single(build_redis_pool(redis_url).await);
+    }
+
+    let nodes: Vec<String> = env::var("REDIS_CLUSTER_NODES")
+        .unwrap_or_else(|_| redis_url.to_string())
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .collect();
+
+    info!("Connecting to Redis cluster with {} node(s)", nodes.len());
+
+    let cluster_client = ClusterClient::new(nodes).unwrap();
+    let cluster_conn = cluster_client.get_async_connection().await.unwrap();
+
+    RedisBackend::Cluster(cluster_conn)
+}
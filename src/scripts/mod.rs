@@ -0,0 +1,135 @@
+use redis::Script;
+
+const RESERVE_KEY_SCRIPT: &str = include_str!("reserve_key.lua");
+const GET_URL_AND_INCR_CLICKS_SCRIPT: &str = include_str!("get_url_and_incr_clicks.lua");
+const RESERVE_HASH_KEY_SCRIPT: &str = include_str!("reserve_hash_key.lua");
+
+/// Atomically reserves `short_key` if it doesn't already exist, storing `url`
+/// and `token` and setting its TTL in the same call. Returns 1 when this call
+/// created the key, 0 when it already existed (and nothing was written).
+pub fn reserve_key_script() -> Script {
+    Script::new(RESERVE_KEY_SCRIPT)
+}
+
+/// Atomically reads the stored `url` and increments `clicks` for `short_key`,
+/// but only if the key still exists. Returns `None` if it doesn't.
+pub fn get_url_and_incr_clicks_script() -> Script {
+    Script::new(GET_URL_AND_INCR_CLICKS_SCRIPT)
+}
+
+/// Atomically reserves a hash-derived `short_key` for `url`, or reports whether
+/// the existing occupant matches `url` (idempotent hit) or collides with it.
+pub fn reserve_hash_key_script() -> Script {
+    Script::new(RESERVE_HASH_KEY_SCRIPT)
+}
+
+/// These exercise the scripts against a real Redis (see `crate::test_support`)
+/// so what their doc comments say one call does (the fields written, the no-op
+/// on an existing key, `None` for a missing key) is checked. They make one call
+/// at a time; concurrent callers are not exercised.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_redis_backend;
+    use redis::AsyncCommands;
+
+    #[tokio::test]
+    async fn reserve_key_sets_url_token_and_clicks_atomically() {
+        let backend = test_redis_backend().await;
+        let mut conn = backend.checkout().await.unwrap();
+        let key = "test:reserve_key:atomic";
+        let _: () = conn.del(key).await.unwrap();
+
+        let reserved: i32 = reserve_key_script()
+            .key(key)
+            .arg("https://example.com")
+            .arg("tok-123")
+            .arg(60)
+            .invoke_async(&mut conn)
+            .await
+            .unwrap();
+        assert_eq!(reserved, 1);
+
+        let url: String = conn.hget(key, "url").await.unwrap();
+        let token: String = conn.hget(key, "token").await.unwrap();
+        let clicks: i64 = conn.hget(key, "clicks").await.unwrap();
+        assert_eq!(url, "https://example.com");
+        assert_eq!(token, "tok-123");
+        assert_eq!(clicks, 0);
+
+        let _: () = conn.del(key).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn reserve_key_is_a_no_op_when_already_reserved() {
+        let backend = test_redis_backend().await;
+        let mut conn = backend.checkout().await.unwrap();
+        let key = "test:reserve_key:existing";
+        let _: () = conn.del(key).await.unwrap();
+
+        let first: i32 = reserve_key_script()
+            .key(key)
+            .arg("https://example.com")
+            .arg("tok-first")
+            .arg(60)
+            .invoke_async(&mut conn)
+            .await
+            .unwrap();
+        assert_eq!(first, 1);
+
+        let second: i32 = reserve_key_script()
+            .key(key)
+            .arg("https://other.example.com")
+            .arg("tok-second")
+            .arg(60)
+            .invoke_async(&mut conn)
+            .await
+            .unwrap();
+        assert_eq!(second, 0);
+
+        let url: String = conn.hget(key, "url").await.unwrap();
+        let token: String = conn.hget(key, "token").await.unwrap();
+        assert_eq!(url, "https://example.com");
+        assert_eq!(token, "tok-first");
+
+        let _: () = conn.del(key).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn get_url_and_incr_clicks_returns_none_for_missing_key() {
+        let backend = test_redis_backend().await;
+        let mut conn = backend.checkout().await.unwrap();
+        let key = "test:incr_clicks:missing";
+        let _: () = conn.del(key).await.unwrap();
+
+        let result: Option<String> = get_url_and_incr_clicks_script()
+            .key(key)
+            .invoke_async(&mut conn)
+            .await
+            .unwrap();
+
+        assert_eq!(result, None);
+    }
+
+    #[tokio::test]
+    async fn get_url_and_incr_clicks_bumps_the_counter() {
+        let backend = test_redis_backend().await;
+        let mut conn = backend.checkout().await.unwrap();
+        let key = "test:incr_clicks:present";
+        let _: () = conn.del(key).await.unwrap();
+        let _: () = conn.hset(key, "url", "https://example.com").await.unwrap();
+        let _: () = conn.hset(key, "clicks", 0).await.unwrap();
+
+        let result: Option<String> = get_url_and_incr_clicks_script()
+            .key(key)
+            .invoke_async(&mut conn)
+            .await
+            .unwrap();
+        assert_eq!(result, Some("https://example.com".to_string()));
+
+        let clicks: i64 = conn.hget(key, "clicks").await.unwrap();
+        assert_eq!(clicks, 1);
+
+        let _: () = conn.del(key).await.unwrap();
+    }
+}
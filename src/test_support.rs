@@ -0,0 +1,18 @@
+//! Shared helper for tests that exercise real Redis behavior (atomicity of
+//! the Lua scripts, the hash-collision retry loop, link management). Panics
+//! rather than skipping when Redis isn't reachable, so a missing Redis fails
+//! the run loudly instead of reporting a false pass. CI provisions one via
+//! the `redis` service container in `.github/workflows/ci.yml`.
+#![cfg(test)]
+
+use crate::redis_backend::{build_redis_backend, RedisBackend};
+
+pub(crate) async fn test_redis_backend() -> RedisBackend {
+    let redis_url = std::env::var("REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1/".to_string());
+    let backend = build_redis_backend(&redis_url).await;
+    backend
+        .checkout()
+        .await
+        .unwrap_or_else(|e| panic!("Redis not reachable at {redis_url} for tests: {e:?}"));
+    backend
+}